@@ -1,7 +1,47 @@
-use std::time::Instant;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
+use crate::devices::vic::{Interrupt, VicManager};
 use crate::memory::{MemResult, MemResultExt, Memory};
 
+/// Source of monotonic emulated time, expressed in nanoseconds.
+///
+/// Timers read elapsed time through this trait instead of `Instant::now()`,
+/// so that emulation is deterministic and freezes whenever the CPU is
+/// halted (e.g. at a GDB breakpoint) instead of continuing to advance with
+/// the wall clock.
+pub trait TimeSource {
+    /// Nanoseconds elapsed since some arbitrary epoch.
+    fn now_nanos(&self) -> u64;
+}
+
+/// A [`TimeSource`] backed by a counter the emulator core advances as it
+/// executes CPU cycles, and freezes whenever execution is halted. Cloning
+/// shares the same underlying counter, so one `VirtualTimeSource` can be
+/// handed out to every timer in the system.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualTimeSource {
+    nanos: Rc<Cell<u64>>,
+}
+
+impl VirtualTimeSource {
+    /// Create a new clock, starting at zero.
+    pub fn new() -> VirtualTimeSource {
+        VirtualTimeSource::default()
+    }
+
+    /// Advance the clock by the given number of nanoseconds.
+    pub fn advance(&self, nanos: u64) {
+        self.nanos.set(self.nanos.get() + nanos);
+    }
+}
+
+impl TimeSource for VirtualTimeSource {
+    fn now_nanos(&self) -> u64 {
+        self.nanos.get()
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 enum Mode {
     FreeRunning = 0,
@@ -28,8 +68,12 @@ pub struct Timer {
     clksel: Clock,
     // implementation details
     wrapmask: u32, // 0x0000FFFF for 16 bit timers, 0xFFFFFFFF for 32 bit timers
-    last_time: Instant,
+    time_source: Rc<dyn TimeSource>,
+    last_time_nanos: u64,
     microticks: u32,
+    interrupt_pending: bool,
+    interrupt: Interrupt,
+    vic: Rc<RefCell<VicManager>>,
 }
 
 impl std::fmt::Debug for Timer {
@@ -40,7 +84,13 @@ impl std::fmt::Debug for Timer {
 
 impl Timer {
     /// Create a new Timer
-    pub fn new(label: &'static str, bits: usize) -> Timer {
+    pub fn new(
+        label: &'static str,
+        bits: usize,
+        interrupt: Interrupt,
+        vic: Rc<RefCell<VicManager>>,
+        time_source: Rc<dyn TimeSource>,
+    ) -> Timer {
         Timer {
             label,
             loadval: None,
@@ -49,17 +99,33 @@ impl Timer {
             mode: Mode::FreeRunning,
             clksel: Clock::Khz2,
             wrapmask: ((1u64 << bits) - 1) as u32,
-            last_time: Instant::now(),
+            last_time_nanos: time_source.now_nanos(),
+            time_source,
             microticks: 0,
+            interrupt_pending: false,
+            interrupt,
+            vic,
         }
     }
 
+    /// Whether the timer has an unacknowledged underflow interrupt pending.
+    pub fn interrupt_pending(&self) -> bool {
+        self.interrupt_pending
+    }
+
+    /// Raise the timer's interrupt, both locally and on the VIC it's wired
+    /// to.
+    fn raise_interrupt(&mut self) {
+        self.interrupt_pending = true;
+        self.vic.borrow_mut().assert_interrupt(self.interrupt.source());
+    }
+
     /// Lazily update the registers on read / write.
     fn update_regs(&mut self) {
         // calculate the time delta
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_time).as_nanos() as u64;
-        self.last_time = now;
+        let now = self.time_source.now_nanos();
+        let dt = now.saturating_sub(self.last_time_nanos);
+        self.last_time_nanos = now;
 
         if !self.enabled {
             return;
@@ -79,9 +145,18 @@ impl Timer {
 
         match self.mode {
             Mode::FreeRunning => {
+                // `wrapping_sub` masked to `wrapmask` is correct modular arithmetic no
+                // matter how many times `ticks` wraps the counter around in one lazy
+                // update (e.g. if firmware sleeps on the interrupt instead of polling
+                // Value); `interrupt_pending` is a level flag rather than a counter, so
+                // coalescing any number of wraps in the gap into a single pending
+                // interrupt is the correct behavior here.
+                let wrapped = ticks > self.val;
                 self.val = self.val.wrapping_sub(ticks) & self.wrapmask;
+                if wrapped {
+                    self.raise_interrupt();
+                }
             }
-            // XXX: double check this code...
             Mode::Periodic => {
                 if self.val >= ticks {
                     self.val -= ticks;
@@ -91,7 +166,16 @@ impl Timer {
                         None => panic!("trying to use unset load value with {}", self.label),
                     };
                     let remaining_ticks = ticks - self.val;
-                    self.val = loadval - remaining_ticks;
+                    // A single reload only accounts for one period's worth of ticks
+                    // (`loadval + 1`); if the timer goes unread for long enough to
+                    // reload more than once, reduce `remaining_ticks` mod the period
+                    // length first so this can't underflow regardless of the gap.
+                    // As with FreeRunning, any number of reloads in the gap coalesce
+                    // into the single `interrupt_pending` flag.
+                    let period = loadval as u64 + 1;
+                    let remaining_ticks = remaining_ticks as u64 % period;
+                    self.val = loadval - remaining_ticks as u32;
+                    self.raise_interrupt();
                 }
             }
         }
@@ -122,7 +206,7 @@ impl Memory for Timer {
                     | ((self.enabled as u32) << 7);
                 Ok(val)
             }
-            // TODO: implement timer interrupts
+            // CLR_REG is write-only on real hardware; reading it is out of spec.
             0x0C => crate::mem_unimpl!("CLR_REG"),
             _ => crate::mem_unexpected!(),
         }
@@ -174,10 +258,164 @@ impl Memory for Timer {
 
                 Ok(())
             }
-            // TODO: implement timer interrupts
-            0x0C => crate::mem_unimpl!("CLR_REG"),
+            // Writing any value to CLR_REG acknowledges the timer's interrupt.
+            0x0C => {
+                self.interrupt_pending = false;
+                self.vic.borrow_mut().clear_interrupt(self.interrupt.source());
+                Ok(())
+            }
             _ => crate::mem_unexpected!(),
         }
         .mem_ctx(offset, self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENABLE: u32 = 1 << 7;
+    const PERIODIC: u32 = 1 << 6;
+    const FREE_RUNNING: u32 = 0;
+    const KHZ_2: u32 = 0;
+
+    fn new_timer(interrupt: Interrupt) -> (Timer, VirtualTimeSource, Rc<RefCell<VicManager>>) {
+        let clock = VirtualTimeSource::new();
+        let vic = Rc::new(RefCell::new(VicManager::new()));
+        let time_source: Rc<dyn TimeSource> = Rc::new(clock.clone());
+        let timer = Timer::new("test", 16, interrupt, vic.clone(), time_source);
+        (timer, clock, vic)
+    }
+
+    #[test]
+    fn periodic_reload_raises_interrupt() {
+        let (mut timer, clock, vic) = new_timer(Interrupt::Tc1UnderOi);
+        vic.borrow_mut().vic1.w32(0x10, 1 << Interrupt::Tc1UnderOi.source()).unwrap();
+
+        timer.w32(0x00, 10).unwrap();
+        timer.w32(0x08, ENABLE | PERIODIC | KHZ_2).unwrap();
+
+        // Tick down to exactly zero; no underflow yet.
+        clock.advance(5_000_000);
+        assert_eq!(timer.r32(0x04).unwrap(), 0);
+        assert!(!timer.interrupt_pending());
+
+        // One more tick underflows and reloads from `loadval`.
+        clock.advance(500_000);
+        timer.r32(0x04).unwrap();
+        assert!(timer.interrupt_pending());
+        assert!(vic.borrow_mut().vic1.irq());
+    }
+
+    #[test]
+    fn tc3_underflow_routes_through_vic_manager_to_vic2() {
+        // Tc3UnderOi is source 51, which only exists on vic2 (32..=63).
+        let (mut timer, clock, vic) = new_timer(Interrupt::Tc3UnderOi);
+        vic.borrow_mut()
+            .vic2
+            .w32(0x10, 1 << (Interrupt::Tc3UnderOi.source() % 32))
+            .unwrap();
+
+        timer.w32(0x00, 1).unwrap();
+        timer.w32(0x08, ENABLE | FREE_RUNNING | KHZ_2).unwrap();
+
+        clock.advance(1_000_000);
+        timer.r32(0x04).unwrap();
+
+        assert!(timer.interrupt_pending());
+        assert!(vic.borrow_mut().vic2.irq());
+        assert!(!vic.borrow_mut().vic1.irq());
+    }
+
+    #[test]
+    fn free_running_wrap_raises_interrupt() {
+        let (mut timer, clock, vic) = new_timer(Interrupt::Tc2UnderOi);
+        vic.borrow_mut().vic1.w32(0x10, 1 << Interrupt::Tc2UnderOi.source()).unwrap();
+
+        timer.w32(0x00, 1).unwrap();
+        timer.w32(0x08, ENABLE | FREE_RUNNING | KHZ_2).unwrap();
+
+        // 2 ticks elapse (1ms @ 2kHz) against a value of 1: wraps past zero.
+        clock.advance(1_000_000);
+        timer.r32(0x04).unwrap();
+        assert!(timer.interrupt_pending());
+        assert!(vic.borrow_mut().vic1.irq());
+    }
+
+    #[test]
+    fn clr_reg_write_acknowledges_interrupt() {
+        let (mut timer, clock, vic) = new_timer(Interrupt::Tc1UnderOi);
+        vic.borrow_mut().vic1.w32(0x10, 1 << Interrupt::Tc1UnderOi.source()).unwrap();
+
+        timer.w32(0x00, 1).unwrap();
+        timer.w32(0x08, ENABLE | FREE_RUNNING | KHZ_2).unwrap();
+        clock.advance(1_000_000);
+        timer.r32(0x04).unwrap();
+        assert!(timer.interrupt_pending());
+
+        timer.w32(0x0C, 0).unwrap();
+        assert!(!timer.interrupt_pending());
+        assert!(!vic.borrow_mut().vic1.irq());
+    }
+
+    #[test]
+    fn periodic_survives_a_gap_spanning_many_reload_periods() {
+        let (mut timer, clock, _vic) = new_timer(Interrupt::Tc1UnderOi);
+
+        timer.w32(0x00, 5).unwrap();
+        timer.w32(0x08, ENABLE | PERIODIC | KHZ_2).unwrap();
+
+        // 1 second @ 2kHz is 2000 ticks, reloading many times over from a
+        // load value of 5 without ever being read in between.
+        clock.advance(1_000_000_000);
+        assert_eq!(timer.r32(0x04).unwrap(), 2);
+        assert!(timer.interrupt_pending());
+    }
+
+    #[test]
+    fn free_running_survives_a_gap_spanning_many_wraps() {
+        let (mut timer, clock, _vic) = new_timer(Interrupt::Tc2UnderOi);
+
+        timer.w32(0x00, 5).unwrap();
+        timer.w32(0x08, ENABLE | FREE_RUNNING | KHZ_2).unwrap();
+
+        // 1 second @ 2kHz is 2000 ticks against a 16 bit (mod 0x10000)
+        // counter that started at 5, wrapping around several times.
+        clock.advance(1_000_000_000);
+        assert_eq!(timer.r32(0x04).unwrap(), (5u32.wrapping_sub(2000)) & 0xFFFF);
+        assert!(timer.interrupt_pending());
+    }
+
+    #[test]
+    fn virtual_time_source_starts_at_zero_and_accumulates() {
+        let clock = VirtualTimeSource::new();
+        assert_eq!(clock.now_nanos(), 0);
+
+        clock.advance(42);
+        clock.advance(8);
+        assert_eq!(clock.now_nanos(), 50);
+    }
+
+    #[test]
+    fn virtual_time_source_clones_share_the_same_counter() {
+        let clock = VirtualTimeSource::new();
+        let shared = clock.clone();
+
+        clock.advance(100);
+        assert_eq!(shared.now_nanos(), 100);
+    }
+
+    #[test]
+    fn paused_clock_freezes_timer_progress() {
+        let (mut timer, _clock, _vic) = new_timer(Interrupt::Tc1UnderOi);
+
+        timer.w32(0x00, 10).unwrap();
+        timer.w32(0x08, ENABLE | PERIODIC | KHZ_2).unwrap();
+
+        // With the clock never advanced (as if the CPU were halted at a
+        // breakpoint), repeated reads must not tick the timer down.
+        assert_eq!(timer.r32(0x04).unwrap(), 10);
+        assert_eq!(timer.r32(0x04).unwrap(), 10);
+        assert!(!timer.interrupt_pending());
+    }
+}
@@ -0,0 +1,25 @@
+//! Hardware interrupt source identifiers.
+
+/// Identifies a hardware interrupt source using the EP93xx's flat 64-source
+/// numbering (sources `0..=31` live on VIC1, `32..=63` on VIC2). Routed
+/// through a [`VicManager`](super::VicManager), which dispatches each
+/// source to the `Vic` that owns it.
+///
+/// Only sources used by devices currently implemented are listed here; this
+/// should grow as more peripherals come online.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Timer 1 (16 bit) underflow
+    Tc1UnderOi = 4,
+    /// Timer 2 (16 bit) underflow
+    Tc2UnderOi = 5,
+    /// Timer 3 (32 bit) underflow
+    Tc3UnderOi = 51,
+}
+
+impl Interrupt {
+    /// The interrupt's position in the flat 64-source vector space.
+    pub fn source(self) -> u8 {
+        self as u8
+    }
+}
@@ -28,6 +28,12 @@ pub struct Vic {
     default_isr: u32,
 
     vector_entries: [VectorEntry; 16],
+    // Stack of priority levels (indices into `vector_entries`) of
+    // interrupts currently being serviced, innermost (most recent) last.
+    // A `VectAddr` read pushes the level of the interrupt it vectors to; a
+    // `VectAddr` write (end-of-interrupt) pops it.
+    priority_stack: [u8; 16],
+    priority_stack_len: usize,
 }
 
 impl Vic {
@@ -41,6 +47,8 @@ impl Vic {
             software_status: 0,
             default_isr: 0,
             vector_entries: Default::default(),
+            priority_stack: [0; 16],
+            priority_stack_len: 0,
         }
     }
 
@@ -52,9 +60,23 @@ impl Vic {
         self.rawstatus() & self.enabled
     }
 
+    /// Sources whose vector entry priority is masked by the interrupt
+    /// currently in service, i.e. priority equal to or lower than (index
+    /// greater than or equal to) the innermost entry on the priority stack.
+    fn masked_sources(&self) -> u32 {
+        let in_service = match self.priority_stack[..self.priority_stack_len].last() {
+            Some(&level) => level as usize,
+            None => return 0,
+        };
+        self.vector_entries[in_service..]
+            .iter()
+            .filter(|entry| entry.enabled)
+            .fold(0, |mask, entry| mask | (1 << entry.source))
+    }
+
     /// Check if an IRQ should be requested
     pub fn irq(&self) -> bool {
-        (self.enabled_active_interrupts() & !self.select) != 0
+        (self.enabled_active_interrupts() & !self.select & !self.masked_sources()) != 0
     }
 
     /// Check if an FIQ should be requested
@@ -62,24 +84,58 @@ impl Vic {
         (self.enabled_active_interrupts() & self.select) != 0
     }
 
-    fn isr_address(&self) -> u32 {
+    /// The priority index (into `vector_entries`) and ISR address of the
+    /// highest-priority currently-asserted-and-enabled, unmasked interrupt,
+    /// if any.
+    fn highest_priority_irq(&self) -> Option<(usize, u32)> {
+        let irqs = self.enabled_active_interrupts() & !self.select & !self.masked_sources();
+        self.vector_entries.iter().enumerate().find_map(|(i, entry)| {
+            if entry.enabled && (irqs & (1 << entry.source)) != 0 {
+                Some((i, entry.isr_addr))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Read `VectAddr`: returns the ISR address of the highest-priority
+    /// currently-asserted-and-enabled interrupt, and pushes its priority
+    /// level onto the priority stack, masking interrupts of equal or lower
+    /// priority until the matching end-of-interrupt write pops it.
+    fn read_vect_addr(&mut self) -> u32 {
         if self.fiq() || !self.irq() {
-            self.default_isr
-        } else {
-            let irqs = self.enabled_active_interrupts() & !self.select;
-            self.vector_entries
-                .iter()
-                .find_map(|entry| {
-                    if entry.enabled && (irqs & (1 << entry.source)) != 0 {
-                        Some(entry.isr_addr)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(self.default_isr)
+            return self.default_isr;
+        }
+
+        match self.highest_priority_irq() {
+            Some((level, isr_addr)) => {
+                if self.priority_stack_len < self.priority_stack.len() {
+                    self.priority_stack[self.priority_stack_len] = level as u8;
+                    self.priority_stack_len += 1;
+                }
+                isr_addr
+            }
+            None => self.default_isr,
+        }
+    }
+
+    /// Write `VectAddr`: end-of-interrupt, popping the priority stack and
+    /// re-enabling lower-priority sources.
+    fn write_vect_addr(&mut self) {
+        if self.priority_stack_len > 0 {
+            self.priority_stack_len -= 1;
         }
     }
 
+    /// The hardware source id of the interrupt currently being serviced
+    /// (i.e. vectored by a `VectAddr` read not yet matched by an
+    /// end-of-interrupt write), if any.
+    pub fn in_service_source(&self) -> Option<u8> {
+        self.priority_stack[..self.priority_stack_len]
+            .last()
+            .map(|&level| self.vector_entries[level as usize].source)
+    }
+
     /// Request an interrupt from a hardware source
     pub fn assert_interrupt(&mut self, source: u8) {
         self.status |= 1 << source;
@@ -135,7 +191,7 @@ impl Memory for Vic {
             0x1c => Err(InvalidAccess),
             // TODO: enforce that VIC Protection bit must be accessed in privileged mode
             0x20 => Err(StubRead(0)),
-            0x30 => Ok(self.isr_address()),
+            0x30 => Ok(self.read_vect_addr()),
             0x34 => Ok(self.default_isr),
             0x100..=0x13c => {
                 let index = ((offset - 0x100) / 4) as usize;
@@ -169,8 +225,7 @@ impl Memory for Vic {
             // TODO: enforce that VIC Protection bit must be accessed in privileged mode
             0x20 => Err(StubWrite),
             // Writing to this signals to the Vic that the interrupt has been serviced.
-            // We don't implement the behavior that cares about that for now, so no-op.
-            0x30 => Ok(()),
+            0x30 => Ok(self.write_vect_addr()),
             0x34 => Ok(self.default_isr = val),
             0x100..=0x13c => {
                 let index = ((offset - 0x100) / 4) as usize;
@@ -192,3 +247,64 @@ impl Memory for Vic {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `Vic` with two vectored, enabled sources: priority 0 (highest,
+    /// source 0, isr 0x1000) and priority 1 (source 1, isr 0x2000).
+    fn vic_with_entries() -> Vic {
+        let mut vic = Vic::new("test");
+        vic.w32(0x200, 0x20).unwrap(); // priority 0: enabled, source 0
+        vic.w32(0x100, 0x1000).unwrap();
+        vic.w32(0x204, 0x20 | 1).unwrap(); // priority 1: enabled, source 1
+        vic.w32(0x104, 0x2000).unwrap();
+        vic.w32(0x10, 0b11).unwrap(); // IntEnable: sources 0 and 1
+        vic
+    }
+
+    #[test]
+    fn vect_addr_read_services_highest_priority_and_masks_it() {
+        let mut vic = vic_with_entries();
+        vic.assert_interrupt(1);
+        assert!(vic.irq());
+
+        assert_eq!(vic.r32(0x30).unwrap(), 0x2000);
+        // Now in service: priority 1 masks itself (equal-or-lower priority).
+        assert!(!vic.irq());
+    }
+
+    #[test]
+    fn higher_priority_interrupt_preempts_one_in_service() {
+        let mut vic = vic_with_entries();
+        vic.assert_interrupt(1);
+        vic.r32(0x30).unwrap(); // service priority 1 (source 1)
+        assert!(!vic.irq());
+
+        vic.assert_interrupt(0); // a strictly higher-priority source fires
+        assert!(vic.irq());
+        assert_eq!(vic.r32(0x30).unwrap(), 0x1000);
+    }
+
+    #[test]
+    fn eoi_write_pops_stack_and_unmasks_lower_priority() {
+        let mut vic = vic_with_entries();
+        vic.assert_interrupt(1);
+        vic.r32(0x30).unwrap();
+        assert!(!vic.irq());
+
+        vic.w32(0x30, 0).unwrap(); // end-of-interrupt
+        assert!(vic.irq());
+    }
+
+    #[test]
+    fn vect_addr_read_with_nothing_active_returns_default_without_pushing() {
+        let mut vic = Vic::new("test");
+        vic.w32(0x34, 0xDEAD).unwrap();
+
+        assert_eq!(vic.r32(0x30).unwrap(), 0xDEAD);
+        // An EOI write with nothing in service must not underflow the stack.
+        vic.w32(0x30, 0).unwrap();
+    }
+}
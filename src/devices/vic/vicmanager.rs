@@ -0,0 +1,180 @@
+use super::Vic;
+use crate::memory::{MemResult, Memory};
+
+/// Source on VIC1 used to signal that VIC2 has an enabled, active
+/// interrupt. Matches the EP93xx's daisy-chained cascade wiring.
+const CASCADE_SOURCE: u8 = 31;
+
+/// Manages the EP93xx's two cascaded PL192 VICs as a single 64-source
+/// interrupt controller.
+///
+/// Sources `0..=31` are routed to `vic1`, and `32..=63` to `vic2`. VIC2's
+/// combined output is reflected onto [`CASCADE_SOURCE`] on VIC1, and a
+/// `VectAddr` read on VIC1 that resolves to the cascade transparently
+/// forwards to VIC2.
+#[derive(Debug)]
+pub struct VicManager {
+    pub vic1: Vic,
+    pub vic2: Vic,
+}
+
+impl VicManager {
+    /// Create a new VicManager
+    pub fn new() -> VicManager {
+        VicManager {
+            vic1: Vic::new("vic1"),
+            vic2: Vic::new("vic2"),
+        }
+    }
+
+    /// Request an interrupt from a hardware source, given its position in
+    /// the flat 0..=63 source space.
+    pub fn assert_interrupt(&mut self, source: u8) {
+        self.route(source).assert_interrupt(source % 32);
+        self.update_cascade();
+    }
+
+    /// Clear an interrupt from a hardware source, given its position in the
+    /// flat 0..=63 source space.
+    pub fn clear_interrupt(&mut self, source: u8) {
+        self.route(source).clear_interrupt(source % 32);
+        self.update_cascade();
+    }
+
+    fn route(&mut self, source: u8) -> &mut Vic {
+        if source < 32 {
+            &mut self.vic1
+        } else {
+            &mut self.vic2
+        }
+    }
+
+    /// Reflect VIC2's combined output onto VIC1's cascade source line.
+    fn update_cascade(&mut self) {
+        if self.vic2.irq() || self.vic2.fiq() {
+            self.vic1.assert_interrupt(CASCADE_SOURCE);
+        } else {
+            self.vic1.clear_interrupt(CASCADE_SOURCE);
+        }
+    }
+
+    /// Handle a `r32` on VIC1's register bank, transparently forwarding
+    /// `VectAddr` reads that resolve to the cascade down to VIC2.
+    pub fn vic1_r32(&mut self, offset: u32) -> MemResult<u32> {
+        // A cascade already in service masks itself, so a read that leaves it
+        // in service only resolved to the cascade if it wasn't there before.
+        let was_cascade = self.vic1.in_service_source() == Some(CASCADE_SOURCE);
+        let val = self.vic1.r32(offset)?;
+        if offset == 0x30
+            && !was_cascade
+            && self.vic1.in_service_source() == Some(CASCADE_SOURCE)
+        {
+            let val = self.vic2.r32(0x30);
+            self.update_cascade();
+            return val;
+        }
+        Ok(val)
+    }
+
+    /// Handle a `w32` on VIC1's register bank, forwarding the
+    /// end-of-interrupt write to VIC2 if the vector currently in service
+    /// was forwarded from it.
+    pub fn vic1_w32(&mut self, offset: u32, val: u32) -> MemResult<()> {
+        if offset == 0x30 && self.vic1.in_service_source() == Some(CASCADE_SOURCE) {
+            self.vic2.w32(0x30, val)?;
+            self.update_cascade();
+        }
+        self.vic1.w32(offset, val)
+    }
+
+    /// Handle a `r32` on VIC2's register bank.
+    pub fn vic2_r32(&mut self, offset: u32) -> MemResult<u32> {
+        let val = self.vic2.r32(offset)?;
+        self.update_cascade();
+        Ok(val)
+    }
+
+    /// Handle a `w32` on VIC2's register bank.
+    pub fn vic2_w32(&mut self, offset: u32, val: u32) -> MemResult<()> {
+        self.vic2.w32(offset, val)?;
+        self.update_cascade();
+        Ok(())
+    }
+}
+
+impl Default for VicManager {
+    fn default() -> VicManager {
+        VicManager::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Configure a single vectored, enabled entry at priority 0 on `vic`.
+    fn vector_source(vic: &mut Vic, source: u8, isr_addr: u32) {
+        vic.w32(0x200, 0x20 | source as u32).unwrap();
+        vic.w32(0x100, isr_addr).unwrap();
+    }
+
+    #[test]
+    fn high_sources_route_to_vic2_and_raise_the_cascade_on_vic1() {
+        let mut mgr = VicManager::new();
+        mgr.vic2_w32(0x10, 1 << 3).unwrap(); // enable source 3 on vic2
+
+        mgr.assert_interrupt(32 + 3); // flat source 35
+
+        assert!(mgr.vic2.irq());
+        assert_eq!(mgr.vic1.r32(0x08).unwrap() & (1 << CASCADE_SOURCE), 1 << CASCADE_SOURCE);
+    }
+
+    #[test]
+    fn clearing_the_last_vic2_source_drops_the_cascade_on_vic1() {
+        let mut mgr = VicManager::new();
+        mgr.vic2_w32(0x10, 1 << 3).unwrap();
+        mgr.assert_interrupt(32 + 3);
+        assert_eq!(mgr.vic1.r32(0x08).unwrap() & (1 << CASCADE_SOURCE), 1 << CASCADE_SOURCE);
+
+        mgr.clear_interrupt(32 + 3);
+
+        assert!(!mgr.vic2.irq());
+        assert_eq!(mgr.vic1.r32(0x08).unwrap() & (1 << CASCADE_SOURCE), 0);
+    }
+
+    #[test]
+    fn vic1_vect_addr_read_forwards_to_vic2_when_cascade_in_service() {
+        let mut mgr = VicManager::new();
+        vector_source(&mut mgr.vic2, 0, 0x5555);
+        mgr.vic2_w32(0x10, 1).unwrap();
+
+        // VIC1 vectors the cascade source, as firmware configuring both VICs would.
+        vector_source(&mut mgr.vic1, CASCADE_SOURCE, 0xAAAA);
+        mgr.vic1.w32(0x10, 1 << CASCADE_SOURCE).unwrap();
+
+        mgr.assert_interrupt(32); // flat source 32 -> vic2 source 0
+        assert!(mgr.vic1.irq());
+
+        // Forwarded from VIC2, not VIC1's own (placeholder) isr address.
+        assert_eq!(mgr.vic1_r32(0x30).unwrap(), 0x5555);
+        assert!(!mgr.vic1.irq()); // cascade now masked/cleared while in service
+    }
+
+    #[test]
+    fn vic1_vect_addr_write_forwards_eoi_to_vic2() {
+        let mut mgr = VicManager::new();
+        vector_source(&mut mgr.vic2, 0, 0x5555);
+        mgr.vic2_w32(0x10, 1).unwrap();
+
+        vector_source(&mut mgr.vic1, CASCADE_SOURCE, 0xAAAA);
+        mgr.vic1.w32(0x10, 1 << CASCADE_SOURCE).unwrap();
+
+        mgr.assert_interrupt(32);
+        mgr.vic1_r32(0x30).unwrap(); // service: pushes both vic1's and vic2's stacks
+
+        mgr.vic1_w32(0x30, 0).unwrap(); // EOI on vic1, forwarded to vic2
+
+        assert!(mgr.vic2.in_service_source().is_none());
+        assert!(mgr.vic1.in_service_source().is_none());
+    }
+}